@@ -21,8 +21,23 @@
 
 #![no_std]
 
+#[cfg(feature = "confusables")]
+extern crate alloc;
+
 use self::Mapping::*;
 
+#[cfg(feature = "confusables")]
+mod skeleton;
+#[cfg(feature = "confusables")]
+pub use skeleton::skeleton;
+
+/// Size of a block in the two-stage `MAPPING_BLOCK_OFFSETS` / `MAPPING_BLOCKS`
+/// trie. Must match the block size the table-generation script used when it
+/// baked `uts46_mapping_table.rs`.
+const BLOCK_SIZE: u32 = 1 << 7;
+const MASK: u32 = BLOCK_SIZE - 1;
+const SHIFT: u32 = MASK.count_ones();
+
 include!("uts46_mapping_table.rs");
 
 #[derive(Debug)]
@@ -49,24 +64,76 @@ enum Mapping {
     Ignored,
     Mapped(StringTableSlice),
     Disallowed,
+    /// One of the four UTS 46 deviation characters (U+00DF, U+03C2, ZWNJ,
+    /// ZWJ). Carries the transitional mapping, which is applied only when
+    /// `Mapper` is configured for transitional processing; otherwise the
+    /// deviation character is treated as `Valid`.
+    Deviation(StringTableSlice),
 }
 
+// `MAPPING_BLOCK_OFFSETS`, `MAPPING_BLOCKS`, and `LAST_CODEPOINT` come from the
+// generated `uts46_mapping_table.rs` and use the same two-stage block scheme as
+// `unicode-joining-type`'s `get_joining_type`: the generator deduplicates identical
+// `BLOCK_SIZE`-sized blocks of `MAPPING_BLOCKS`, so many high codepoint ranges (e.g.
+// private-use and unassigned areas) share a single `Disallowed` block instead of each
+// getting their own entry in `MAPPING_BLOCK_OFFSETS`.
+const DISALLOWED: Mapping = Mapping::Disallowed;
+
 fn find_char(codepoint: char) -> &'static Mapping {
-    let idx = match TABLE.binary_search_by_key(&codepoint, |&val| val.0) {
-        Ok(idx) => idx,
-        Err(idx) => idx - 1,
-    };
+    let u = codepoint as u32;
+    if u > LAST_CODEPOINT {
+        return &DISALLOWED;
+    }
+    let block = MAPPING_BLOCK_OFFSETS[(u >> SHIFT) as usize];
+    &MAPPING_BLOCKS[block as usize + (u & MASK) as usize]
+}
 
-    const SINGLE_MARKER: u16 = 1 << 15;
+/// The result of classifying a single input codepoint, shared between
+/// `Mapper::next` and `ReportingMapper::next` so the UseSTD3ASCIIRules /
+/// transitional-processing logic lives in one place.
+enum Classification {
+    /// Passes through as-is (includes the LDH fast path, `Valid`, and
+    /// non-transitional `Deviation`).
+    Char(char),
+    Ignored,
+    /// Disallowed per the mapping table itself.
+    Disallowed,
+    /// Allowed under relaxed processing, but disallowed because
+    /// `use_std3_ascii_rules` is set and this is non-LDH ASCII.
+    Std3Disallowed,
+    /// Expands to a string (covers `Mapped` and transitional `Deviation`).
+    Expand(&'static str),
+}
 
-    let (base, x) = TABLE[idx];
-    let single = (x & SINGLE_MARKER) != 0;
-    let offset = !SINGLE_MARKER & x;
+/// Classifies `codepoint` per UTS 46, given the two `Mapper` /
+/// `ReportingMapper` configuration flags that affect classification.
+///
+/// Case folding and other `Mapped` entries are not governed by
+/// `use_std3_ascii_rules`; that flag only downgrades what would otherwise be
+/// `Valid` non-LDH ASCII (e.g. `_`) to disallowed.
+fn classify(codepoint: char, transitional: bool, use_std3_ascii_rules: bool) -> Classification {
+    if let '.' | '-' | 'a'..='z' | '0'..='9' = codepoint {
+        return Classification::Char(codepoint);
+    }
 
-    if single {
-        &MAPPING_TABLE[offset as usize]
-    } else {
-        &MAPPING_TABLE[(offset + (codepoint as u16 - base as u16)) as usize]
+    match *find_char(codepoint) {
+        Mapping::Valid => {
+            if use_std3_ascii_rules && codepoint.is_ascii() {
+                Classification::Std3Disallowed
+            } else {
+                Classification::Char(codepoint)
+            }
+        }
+        Mapping::Ignored => Classification::Ignored,
+        Mapping::Mapped(ref slice) => Classification::Expand(decode_slice(slice)),
+        Mapping::Disallowed => Classification::Disallowed,
+        Mapping::Deviation(ref slice) => {
+            if transitional {
+                Classification::Expand(decode_slice(slice))
+            } else {
+                Classification::Char(codepoint)
+            }
+        }
     }
 }
 
@@ -77,17 +144,44 @@ where
     chars: I,
     slice: Option<core::str::Chars<'static>>,
     ignored_as_errors: bool,
+    transitional: bool,
+    use_std3_ascii_rules: bool,
 }
 
 impl<I> Mapper<I>
 where
     I: Iterator<Item = char>,
 {
-    pub fn new(delegate: I, ignored_as_errors: bool) -> Self {
+    pub fn new(
+        delegate: I,
+        ignored_as_errors: bool,
+        transitional: bool,
+        use_std3_ascii_rules: bool,
+    ) -> Self {
         Mapper {
             chars: delegate,
             slice: None,
             ignored_as_errors,
+            transitional,
+            use_std3_ascii_rules,
+        }
+    }
+
+    /// Like `new`, but the returned iterator reports the position and cause
+    /// of each mapping failure instead of silently substituting U+FFFD.
+    pub fn new_reporting(
+        delegate: I,
+        ignored_as_errors: bool,
+        transitional: bool,
+        use_std3_ascii_rules: bool,
+    ) -> ReportingMapper<I> {
+        ReportingMapper {
+            chars: delegate,
+            slice: None,
+            index: 0,
+            ignored_as_errors,
+            transitional,
+            use_std3_ascii_rules,
         }
     }
 }
@@ -110,25 +204,121 @@ where
             }
 
             let codepoint = self.chars.next()?;
-            if let '.' | '-' | 'a'..='z' | '0'..='9' = codepoint {
-                return Some(codepoint);
-            }
 
-            return Some(match *find_char(codepoint) {
-                Mapping::Valid => codepoint,
-                Mapping::Ignored => {
-                    if self.ignored_as_errors {
-                        '\u{FFFD}'
-                    } else {
+            return Some(
+                match classify(codepoint, self.transitional, self.use_std3_ascii_rules) {
+                    Classification::Char(c) => c,
+                    Classification::Ignored => {
+                        if self.ignored_as_errors {
+                            '\u{FFFD}'
+                        } else {
+                            continue;
+                        }
+                    }
+                    Classification::Disallowed | Classification::Std3Disallowed => '\u{FFFD}',
+                    Classification::Expand(s) => {
+                        self.slice = Some(s.chars());
                         continue;
                     }
+                },
+            );
+        }
+    }
+}
+
+/// Why a character could not be mapped by [`ReportingMapper`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MappingErrorReason {
+    /// The character is disallowed in UTS 46 processing.
+    Disallowed,
+    /// The character is ignored (mapped to nothing), and the `ReportingMapper`
+    /// was configured to treat ignored characters as errors.
+    Ignored,
+    /// The character is ASCII but outside the LDH set, and the
+    /// `ReportingMapper` was configured with `use_std3_ascii_rules`.
+    Std3Disallowed,
+}
+
+/// A mapping failure reported by [`ReportingMapper`]: which input character
+/// caused it, at what position in the input, and why.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MappingError {
+    /// The offending input character.
+    pub char: char,
+    /// The index of `char` among the characters yielded by the delegate
+    /// iterator (not a byte offset).
+    pub index: usize,
+    /// Why `char` could not be mapped.
+    pub reason: MappingErrorReason,
+}
+
+/// Like [`Mapper`], but instead of silently substituting U+FFFD for
+/// characters that fail to map, yields a [`MappingError`] identifying the
+/// offending character, its position, and the reason. Returned by
+/// [`Mapper::new_reporting`].
+pub struct ReportingMapper<I>
+where
+    I: Iterator<Item = char>,
+{
+    chars: I,
+    slice: Option<core::str::Chars<'static>>,
+    index: usize,
+    ignored_as_errors: bool,
+    transitional: bool,
+    use_std3_ascii_rules: bool,
+}
+
+impl<I> Iterator for ReportingMapper<I>
+where
+    I: Iterator<Item = char>,
+{
+    type Item = Result<char, MappingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(s) = &mut self.slice {
+                match s.next() {
+                    Some(c) => return Some(Ok(c)),
+                    None => {
+                        self.slice = None;
+                    }
                 }
-                Mapping::Mapped(ref slice) => {
-                    self.slice = Some(decode_slice(slice).chars());
-                    continue;
-                }
-                Mapping::Disallowed => '\u{FFFD}',
-            });
+            }
+
+            let codepoint = self.chars.next()?;
+            let index = self.index;
+            self.index += 1;
+
+            return Some(
+                match classify(codepoint, self.transitional, self.use_std3_ascii_rules) {
+                    Classification::Char(c) => Ok(c),
+                    Classification::Ignored => {
+                        if self.ignored_as_errors {
+                            Err(MappingError {
+                                char: codepoint,
+                                index,
+                                reason: MappingErrorReason::Ignored,
+                            })
+                        } else {
+                            continue;
+                        }
+                    }
+                    Classification::Disallowed => Err(MappingError {
+                        char: codepoint,
+                        index,
+                        reason: MappingErrorReason::Disallowed,
+                    }),
+                    Classification::Std3Disallowed => Err(MappingError {
+                        char: codepoint,
+                        index,
+                        reason: MappingErrorReason::Std3Disallowed,
+                    }),
+                    Classification::Expand(s) => {
+                        self.slice = Some(s.chars());
+                        continue;
+                    }
+                },
+            );
         }
     }
 }
@@ -165,6 +355,36 @@ impl JoiningType {
         JoiningTypeMask(joining_type_to_mask(self.0))
     }
 
+    // `true` iff this value is the Right_Joining value.
+    #[inline(always)]
+    pub fn is_right_joining(self) -> bool {
+        self.0 == unicode_joining_type::JoiningType::RightJoining
+    }
+
+    // `true` iff this value is the Left_Joining value.
+    #[inline(always)]
+    pub fn is_left_joining(self) -> bool {
+        self.0 == unicode_joining_type::JoiningType::LeftJoining
+    }
+
+    // `true` iff this value is the Dual_Joining value.
+    #[inline(always)]
+    pub fn is_dual_joining(self) -> bool {
+        self.0 == unicode_joining_type::JoiningType::DualJoining
+    }
+
+    // `true` iff this value is the Join_Causing value.
+    #[inline(always)]
+    pub fn is_join_causing(self) -> bool {
+        self.0 == unicode_joining_type::JoiningType::JoinCausing
+    }
+
+    // `true` iff this value is the Non_Joining value.
+    #[inline(always)]
+    pub fn is_non_joining(self) -> bool {
+        self.0 == unicode_joining_type::JoiningType::NonJoining
+    }
+
     // `true` iff this value is the Transparent value.
     #[inline(always)]
     pub fn is_transparent(self) -> bool {
@@ -192,6 +412,143 @@ pub fn joining_type(c: char) -> JoiningType {
     JoiningType(unicode_joining_type::get_joining_type(c))
 }
 
+// `JOINING_GROUP_BLOCK_OFFSETS`, `JOINING_GROUP_BLOCKS`, and
+// `JOINING_GROUP_LAST_CODEPOINT` come from the generated
+// `joining_group_table.rs`, parsed from the fourth field of
+// `ArabicShaping.txt`, and use the same two-stage block scheme as
+// `find_char`, above.
+include!("joining_group_table.rs");
+
+/// Value for the Joining_Group Unicode property, as defined in
+/// `ArabicShaping.txt`. Most characters have `NoJoiningGroup`; the rest
+/// are the named Arabic- and Syriac-script groups that ContextJ rules and
+/// Arabic-shaping consumers need to distinguish.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoiningGroup {
+    NoJoiningGroup,
+    AfricanFeh,
+    AfricanNoon,
+    AfricanQaf,
+    Ain,
+    Alaph,
+    Alef,
+    Beh,
+    Beth,
+    BurushaskiYehBarree,
+    Dal,
+    DalathRish,
+    E,
+    FarsiYeh,
+    Fe,
+    Feh,
+    FinalSemkath,
+    Gaf,
+    Gamal,
+    Hah,
+    HanifiRohingyaKinnaYa,
+    HanifiRohingyaPa,
+    He,
+    Heh,
+    HehGoal,
+    Heth,
+    Kaf,
+    Kaph,
+    Khaph,
+    KnottedHeh,
+    Lam,
+    Lamadh,
+    MalayalamBha,
+    MalayalamJa,
+    MalayalamLla,
+    MalayalamLlla,
+    MalayalamNga,
+    MalayalamNna,
+    MalayalamNnna,
+    MalayalamNya,
+    MalayalamRa,
+    MalayalamSsa,
+    MalayalamTta,
+    ManichaeanAleph,
+    ManichaeanAyin,
+    ManichaeanBeth,
+    ManichaeanDaleth,
+    ManichaeanDhamedh,
+    ManichaeanFive,
+    ManichaeanGimel,
+    ManichaeanHeth,
+    ManichaeanHundred,
+    ManichaeanKaph,
+    ManichaeanLamedh,
+    ManichaeanMem,
+    ManichaeanNun,
+    ManichaeanOne,
+    ManichaeanPe,
+    ManichaeanQoph,
+    ManichaeanResh,
+    ManichaeanSadhe,
+    ManichaeanSamekh,
+    ManichaeanTaw,
+    ManichaeanTen,
+    ManichaeanTeth,
+    ManichaeanThamedh,
+    ManichaeanTwenty,
+    ManichaeanWaw,
+    ManichaeanYodh,
+    ManichaeanZayin,
+    Meem,
+    Mim,
+    Noon,
+    Nun,
+    Nya,
+    Pe,
+    Qaf,
+    Qaph,
+    Reh,
+    ReversedPe,
+    RohingyaYeh,
+    Sad,
+    Sadhe,
+    Seen,
+    Semkath,
+    Shin,
+    StraightWaw,
+    SwashKaf,
+    SyriacWaw,
+    Tah,
+    Taw,
+    TehMarbuta,
+    TehMarbutaGoal,
+    Teth,
+    ThinYeh,
+    VerticalTail,
+    Waw,
+    Yeh,
+    YehBarree,
+    YehWithTail,
+    Yudh,
+    YudhHe,
+    Zain,
+    Zhain,
+}
+
+fn find_joining_group(codepoint: char) -> JoiningGroup {
+    let u = codepoint as u32;
+    if u > JOINING_GROUP_LAST_CODEPOINT {
+        return JoiningGroup::NoJoiningGroup;
+    }
+    let block = JOINING_GROUP_BLOCK_OFFSETS[(u >> SHIFT) as usize];
+    JOINING_GROUP_BLOCKS[block as usize + (u & MASK) as usize]
+}
+
+/// Returns the Joining_Group of `c`, for ContextJ validation and
+/// Arabic-shaping-sensitive consumers that need more than the coarse
+/// Joining_Type.
+#[inline(always)]
+pub fn joining_group(c: char) -> JoiningGroup {
+    find_joining_group(c)
+}
+
 #[cfg(test)]
 mod tests {
     use super::{find_char, Mapping};
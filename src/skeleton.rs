@@ -0,0 +1,61 @@
+// Confusable-skeleton computation per the Unicode confusable-detection
+// algorithm:
+// https://www.unicode.org/reports/tr39/#Confusable_Detection
+
+use alloc::string::String;
+use unicode_normalization::UnicodeNormalization;
+
+#[derive(Debug)]
+struct PrototypeSlice {
+    // Same layout rationale as `StringTableSlice` in `lib.rs`: separate byte
+    // fields keep the struct alignment at 1 so it packs into `Option` tightly.
+    byte_start_lo: u8,
+    byte_start_hi: u8,
+    byte_len: u8,
+}
+
+fn decode_prototype(slice: &PrototypeSlice) -> &'static str {
+    let lo = slice.byte_start_lo as usize;
+    let hi = slice.byte_start_hi as usize;
+    let start = (hi << 8) | lo;
+    let len = slice.byte_len as usize;
+    &PROTOTYPE_STRING_TABLE[start..(start + len)]
+}
+
+// `PROTOTYPE_BLOCK_OFFSETS`, `PROTOTYPE_BLOCKS`, `PROTOTYPE_STRING_TABLE`, and
+// `PROTOTYPE_LAST_CODEPOINT` come from the generated `confusables_table.rs`,
+// parsed from the "MA" (prototype) mappings in `confusables.txt`, and use the
+// same two-stage block scheme as `find_char` in `lib.rs` (sharing its
+// `SHIFT` / `MASK` constants), deduplicating blocks where most codepoints
+// have no confusable prototype.
+include!("confusables_table.rs");
+
+fn find_prototype(codepoint: char) -> Option<&'static str> {
+    let u = codepoint as u32;
+    if u > PROTOTYPE_LAST_CODEPOINT {
+        return None;
+    }
+    let block = PROTOTYPE_BLOCK_OFFSETS[(u >> super::SHIFT) as usize];
+    PROTOTYPE_BLOCKS[block as usize + (u & super::MASK) as usize]
+        .as_ref()
+        .map(decode_prototype)
+}
+
+/// Computes the Unicode confusable skeleton of `input`: NFD-decomposes,
+/// substitutes each character's confusable prototype string where one
+/// exists (falling back to the character itself otherwise), and
+/// NFD-decomposes the result again.
+///
+/// Two strings are confusable, per [UTS #39], iff their skeletons are equal.
+///
+/// [UTS #39]: https://www.unicode.org/reports/tr39/#Confusable_Detection
+pub fn skeleton(input: &str) -> String {
+    let mut substituted = String::new();
+    for c in input.nfd() {
+        match find_prototype(c) {
+            Some(proto) => substituted.push_str(proto),
+            None => substituted.push(c),
+        }
+    }
+    substituted.nfd().collect()
+}